@@ -1,13 +1,30 @@
+// `main` only drives `finalized_stream`; the rest of the SDK surface (verification,
+// block-pinned quorums, chain continuity) is public API for downstream consumers that isn't
+// reachable from this crate's own demo binary.
+#![allow(dead_code)]
+
+use async_stream::try_stream;
+use futures::{Stream, StreamExt};
+use rand::Rng;
 use reqwest::Client;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
-use std::time::SystemTime;
-use bls12_381::{G2Prepared, G2Affine, pairing};
+use std::time::Duration;
+use bls12_381::{
+    hash_to_curve::{ExpandMsgXmd, HashToCurve},
+    multi_miller_loop, pairing, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective,
+};
+use group::Group;
+
+// Domain-separation tag for the BLS signature hash-to-curve suite used by Zellular operators.
+const SIG_DST: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_";
 
-// Struct for operators
+// Wire shape returned by the subgraph. `bls12_381` has no `serde` feature, so this is kept
+// separate from `Operator`: only plain/string fields are deserialized here, and the curve
+// points are decoded and validated afterward by `decode_operator_keys`.
 #[derive(Debug, Deserialize)]
-struct Operator {
+struct OperatorRecord {
     id: String,
     operator_id: String,
     pubkey_g1_x: Vec<String>,
@@ -16,9 +33,66 @@ struct Operator {
     pubkey_g2_y: Vec<String>,
     socket: String,
     stake: f64,
-    public_key_g2: Option<G2Affine>, // Placeholder for G2 affine key
 }
 
+// An operator with its BLS keys decoded and validated. Only ever constructed for operators
+// that passed `decode_operator_keys`, so the keys here are always present and valid.
+#[derive(Debug)]
+struct Operator {
+    id: String,
+    operator_id: String,
+    socket: String,
+    stake: f64,
+    public_key_g1: G1Affine,
+    public_key_g2: G2Affine,
+}
+
+// Typed failures for decoding and validating an operator's registered BLS key pair.
+#[derive(Debug)]
+enum OperatorKeyError {
+    InvalidCoordinate,
+    NotOnCurve,
+    NotInSubgroup,
+    ProofOfPossessionMismatch,
+}
+
+impl std::fmt::Display for OperatorKeyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OperatorKeyError::InvalidCoordinate => {
+                write!(f, "operator public key coordinate is not a valid field element")
+            }
+            OperatorKeyError::NotOnCurve => write!(f, "operator public key is not a point on the curve"),
+            OperatorKeyError::NotInSubgroup => write!(f, "operator public key is not in the correct subgroup"),
+            OperatorKeyError::ProofOfPossessionMismatch => {
+                write!(f, "operator G1 and G2 keys do not share the same secret")
+            }
+        }
+    }
+}
+
+impl std::error::Error for OperatorKeyError {}
+
+// Typed failures for signature verification against a quorum.
+#[derive(Debug)]
+pub enum VerificationError {
+    // A nonsigner id the caller supplied isn't part of this quorum — e.g. it was dropped by
+    // `decode_operator_keys` for failing validation, or never belonged to this operator set.
+    UnknownNonsigner(String),
+}
+
+impl std::fmt::Display for VerificationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VerificationError::UnknownNonsigner(id) => {
+                write!(f, "nonsigner {} is not part of this quorum", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VerificationError {}
+
 // Struct for GraphQL query response
 #[derive(Debug, Deserialize)]
 struct QueryResponse {
@@ -27,7 +101,18 @@ struct QueryResponse {
 
 #[derive(Debug, Deserialize)]
 struct QueryData {
-    operators: Vec<Operator>,
+    operators: Vec<OperatorRecord>,
+    _meta: Meta,
+}
+
+#[derive(Debug, Deserialize)]
+struct Meta {
+    block: MetaBlock,
+}
+
+#[derive(Debug, Deserialize)]
+struct MetaBlock {
+    number: u64,
 }
 
 // Hash function using SHA-256
@@ -37,11 +122,168 @@ fn hash(input: &str) -> String {
     format!("{:x}", hasher.finalize())
 }
 
-// Fetch operators via a GraphQL query
-async fn get_operators(client: &Client) -> Result<HashMap<String, Operator>, Box<dyn std::error::Error>> {
-    let query = r#"
-        query MyQuery {
-          operators {
+// Verify that `leaves` (each a `(generalized_index, leaf_hex)` pair) are included under
+// `batch_root`, given a compact Merkle multiproof. Generalized indices follow the SSZ
+// convention: a node's parent is `index / 2` and its sibling is `index ^ 1`. Nodes are
+// combined bottom-up, pulling each missing sibling from the next unused proof entry, until
+// the root at generalized index 1 is reconstructed. Returns `true` only if that root equals
+// `batch_root` and every proof entry was consumed.
+pub fn verify_inclusion(batch_root: &str, leaves: &[(u64, String)], proof: &[String]) -> bool {
+    if leaves.is_empty() {
+        return false;
+    }
+
+    let mut nodes: HashMap<u64, String> = leaves
+        .iter()
+        .map(|(index, leaf)| (*index, hash(leaf)))
+        .collect();
+
+    let mut proof_iter = proof.iter();
+    // Max-heap on generalized index: every node on a deeper level has a larger index than any
+    // node above it, so always taking the largest pending index combines strictly bottom-up.
+    let mut pending: std::collections::BinaryHeap<u64> = nodes.keys().copied().collect();
+
+    while let Some(index) = pending.pop() {
+        if index <= 1 || !nodes.contains_key(&index) {
+            continue;
+        }
+        let parent = index / 2;
+        if nodes.contains_key(&parent) {
+            continue;
+        }
+
+        let sibling = index ^ 1;
+        let sibling_hash = match nodes.get(&sibling) {
+            Some(known) => known.clone(),
+            None => match proof_iter.next() {
+                Some(next) => next.clone(),
+                None => return false,
+            },
+        };
+        nodes.entry(sibling).or_insert(sibling_hash);
+
+        let (left, right) = if index.is_multiple_of(2) {
+            (nodes[&index].clone(), nodes[&sibling].clone())
+        } else {
+            (nodes[&sibling].clone(), nodes[&index].clone())
+        };
+        let parent_hash = hash(&format!("{}{}", left, right));
+        nodes.insert(parent, parent_hash);
+        pending.push(parent);
+    }
+
+    proof_iter.next().is_none() && nodes.get(&1).map(|root| root == batch_root).unwrap_or(false)
+}
+
+// Decode a hex-encoded, compressed G1 point (the serialized form of a BLS signature).
+fn decode_g1_compressed(signature_hex: &str) -> Option<G1Affine> {
+    let bytes = hex::decode(signature_hex).ok()?;
+    let bytes: [u8; 48] = bytes.try_into().ok()?;
+    G1Affine::from_compressed(&bytes).into()
+}
+
+// Parse a base-10 string into a fixed-width big-endian byte array, e.g. a field element
+// coordinate as returned by the subgraph. Returns `None` if it doesn't fit in `len` bytes.
+fn decimal_to_be_bytes(value: &str, len: usize) -> Option<Vec<u8>> {
+    let mut bytes = vec![0u8; len];
+    for ch in value.trim().chars() {
+        let digit = ch.to_digit(10)? as u16;
+        let mut carry = digit;
+        for byte in bytes.iter_mut().rev() {
+            let total = (*byte as u16) * 10 + carry;
+            *byte = (total & 0xff) as u8;
+            carry = total >> 8;
+        }
+        if carry != 0 {
+            return None;
+        }
+    }
+    Some(bytes)
+}
+
+// Decode and validate an operator's registered (G1, G2) public key pair.
+//
+// G1 coordinates are single Fp elements (48 bytes each); G2 coordinates are Fp2 elements
+// encoded as `[c1, c0]` (96 bytes each), matching the uncompressed point serialization bls12_381
+// expects. Points that are malformed, off-curve, or outside the prime-order subgroup are
+// rejected, as are G1/G2 pairs that don't share a secret (a proof-of-possession check that
+// guards aggregation against rogue-key attacks).
+fn decode_operator_keys(record: &OperatorRecord) -> Result<(G1Affine, G2Affine), OperatorKeyError> {
+    let g1_x = decimal_to_be_bytes(
+        record.pubkey_g1_x.first().ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+    let g1_y = decimal_to_be_bytes(
+        record.pubkey_g1_y.first().ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+
+    let mut g1_bytes = [0u8; 96];
+    g1_bytes[..48].copy_from_slice(&g1_x);
+    g1_bytes[48..].copy_from_slice(&g1_y);
+    let pk_g1: G1Affine =
+        Option::from(G1Affine::from_uncompressed(&g1_bytes)).ok_or(OperatorKeyError::NotOnCurve)?;
+    if !bool::from(pk_g1.is_torsion_free()) {
+        return Err(OperatorKeyError::NotInSubgroup);
+    }
+
+    let g2_x1 = decimal_to_be_bytes(
+        record.pubkey_g2_x.first().ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+    let g2_x0 = decimal_to_be_bytes(
+        record.pubkey_g2_x.get(1).ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+    let g2_y1 = decimal_to_be_bytes(
+        record.pubkey_g2_y.first().ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+    let g2_y0 = decimal_to_be_bytes(
+        record.pubkey_g2_y.get(1).ok_or(OperatorKeyError::InvalidCoordinate)?,
+        48,
+    )
+    .ok_or(OperatorKeyError::InvalidCoordinate)?;
+
+    let mut g2_bytes = [0u8; 192];
+    g2_bytes[..48].copy_from_slice(&g2_x1);
+    g2_bytes[48..96].copy_from_slice(&g2_x0);
+    g2_bytes[96..144].copy_from_slice(&g2_y1);
+    g2_bytes[144..].copy_from_slice(&g2_y0);
+    let pk_g2: G2Affine =
+        Option::from(G2Affine::from_uncompressed(&g2_bytes)).ok_or(OperatorKeyError::NotOnCurve)?;
+    if !bool::from(pk_g2.is_torsion_free()) {
+        return Err(OperatorKeyError::NotInSubgroup);
+    }
+
+    if pairing(&pk_g1, &G2Affine::generator()) != pairing(&G1Affine::generator(), &pk_g2) {
+        return Err(OperatorKeyError::ProofOfPossessionMismatch);
+    }
+
+    Ok((pk_g1, pk_g2))
+}
+
+// Fetch the operator quorum via a GraphQL query, optionally pinned to a specific block so
+// callers can re-derive the quorum that was active when a finalization was signed. Returns
+// the block the subgraph actually resolved the query against alongside the operators, since
+// "latest" only becomes a concrete number once the query has run.
+async fn get_operators(
+    client: &Client,
+    block: Option<u64>,
+) -> Result<(u64, HashMap<String, Operator>), Box<dyn std::error::Error>> {
+    let operators_args = match block {
+        Some(number) => format!("(block: {{ number: {} }})", number),
+        None => String::new(),
+    };
+    let query = format!(
+        r#"
+        query MyQuery {{
+          operators{operators_args} {{
             id
             operatorId
             pubkeyG1_X
@@ -50,9 +292,16 @@ async fn get_operators(client: &Client) -> Result<HashMap<String, Operator>, Box
             pubkeyG2_Y
             socket
             stake
-          }
-        }
-    "#;
+          }}
+          _meta{operators_args} {{
+            block {{
+              number
+            }}
+          }}
+        }}
+    "#,
+        operators_args = operators_args
+    );
 
     let subgraph_url = "https://api.studio.thegraph.com/query/85556/bls_apk_registry/version/latest";
 
@@ -63,95 +312,333 @@ async fn get_operators(client: &Client) -> Result<HashMap<String, Operator>, Box
         .await?;
 
     let response_json: QueryResponse = resp.json().await?;
+    let resolved_block = response_json.data._meta.block.number;
     let mut operators = HashMap::new();
 
-    for mut operator in response_json.data.operators {
-        operator.stake = f64::min(1.0, operator.stake / 10f64.powi(18));
+    for mut record in response_json.data.operators {
+        record.stake = f64::min(1.0, record.stake / 10f64.powi(18));
 
-        // Here, we should set the G2 key (use a proper BLS library for Rust)
-        let public_key_g2 = G2Affine::identity(); // Placeholder
-        operator.public_key_g2 = Some(public_key_g2);
+        let (public_key_g1, public_key_g2) = match decode_operator_keys(&record) {
+            Ok(keys) => keys,
+            Err(err) => {
+                eprintln!("dropping operator {}: {}", record.id, err);
+                continue;
+            }
+        };
+
+        operators.insert(
+            record.id.clone(),
+            Operator {
+                id: record.id,
+                operator_id: record.operator_id,
+                socket: record.socket,
+                stake: record.stake,
+                public_key_g1,
+                public_key_g2,
+            },
+        );
+    }
+
+    Ok((resolved_block, operators))
+}
 
-        operators.insert(operator.id.clone(), operator);
+// Aggregate the G2 public keys of a quorum into a single aggregate public key.
+//
+// bls12_381 only implements Add/Sub for G2Affine against G2Projective, not G2Affine against
+// itself, so accumulation happens in the projective group and is converted back at the end.
+fn aggregate_public_key(operators: &HashMap<String, Operator>) -> G2Affine {
+    let mut aggregated_public_key = G2Projective::identity();
+    for operator in operators.values() {
+        aggregated_public_key += operator.public_key_g2;
     }
+    G2Affine::from(aggregated_public_key)
+}
+
+// The operator set and its aggregate public key as they stood at a specific block.
+struct Quorum {
+    operators: HashMap<String, Operator>,
+    aggregated_public_key: G2Affine,
+}
+
+// A single finalized batch, annotated with its index and cumulative chaining hash.
+#[derive(Debug, Clone)]
+pub struct FinalizedBatch {
+    pub index: i32,
+    pub batch: String,
+    pub chaining_hash: String,
+}
+
+// Failures from the chain-integrity checks in `Zellular::verify_chain`.
+#[derive(Debug)]
+pub enum FinalizationError {
+    // A batch's index wasn't exactly one past the previous batch's index. The indices checked
+    // are the ones `finalized_stream` assigns by counting the batches it receives, not indices
+    // declared by the node, so a node that skips, replaces, or reorders batches does not
+    // trigger this. The node's API also exposes no independent chaining-hash value to check
+    // our local recomputation against, so that recomputation is a checkpoint for callers to
+    // persist and resume from, not something verified against a second source.
+    ChainBreak {
+        expected_index: i32,
+        found_index: i32,
+    },
+    Upstream(Box<dyn std::error::Error>),
+}
 
-    Ok(operators)
+impl std::fmt::Display for FinalizationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FinalizationError::ChainBreak { expected_index, found_index } => {
+                write!(f, "chain break: expected index {}, found index {}", expected_index, found_index)
+            }
+            FinalizationError::Upstream(err) => write!(f, "{}", err),
+        }
+    }
 }
 
+impl std::error::Error for FinalizationError {}
+
 // Struct for Zellular
 struct Zellular {
     app_name: String,
     base_url: String,
     threshold_percent: f64,
-    operators: HashMap<String, Operator>,
-    aggregated_public_key: G2Affine,
+    client: Client,
+    quorum_cache: std::sync::RwLock<HashMap<u64, Quorum>>,
+    latest_block: std::sync::RwLock<u64>,
 }
 
 impl Zellular {
     pub async fn new(app_name: &str, base_url: &str, threshold_percent: f64) -> Result<Self, Box<dyn std::error::Error>> {
         let client = Client::new();
-        let operators = get_operators(&client).await?;
+        let (block, operators) = get_operators(&client, None).await?;
+        let aggregated_public_key = aggregate_public_key(&operators);
 
-        // Aggregate G2 public keys
-        let mut aggregated_public_key = G2Affine::identity();
-        for operator in operators.values() {
-            aggregated_public_key = aggregated_public_key + operator.public_key_g2.unwrap();
-        }
+        let mut quorum_cache = HashMap::new();
+        quorum_cache.insert(
+            block,
+            Quorum {
+                operators,
+                aggregated_public_key,
+            },
+        );
 
         Ok(Self {
             app_name: app_name.to_string(),
             base_url: base_url.to_string(),
             threshold_percent,
-            operators,
-            aggregated_public_key,
+            client,
+            quorum_cache: std::sync::RwLock::new(quorum_cache),
+            latest_block: std::sync::RwLock::new(block),
         })
     }
 
-    // Verify a BLS signature (placeholder, adjust with a real BLS library)
-    pub fn verify_signature(&self, message: &str, signature_hex: &str, nonsigners: Vec<String>) -> bool {
-        let total_stake: f64 = self.operators.values().map(|op| op.stake).sum();
-        let nonsigners_stake: f64 = nonsigners.iter().map(|id| self.operators.get(id).unwrap().stake).sum();
+    // Re-resolve the latest quorum and advance `latest_block` to it, caching the result.
+    // `verify_signature` always reads whatever `latest_block` currently points to, so callers
+    // that want it to track operator registrations/deregistrations/restakes over time (rather
+    // than staying pinned to the quorum seen in `new`) should call this periodically.
+    pub async fn refresh_latest_quorum(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let (block, operators) = get_operators(&self.client, None).await?;
+        let aggregated_public_key = aggregate_public_key(&operators);
 
-        if (100.0 * nonsigners_stake / total_stake) > (100.0 - self.threshold_percent) {
-            return false;
+        self.quorum_cache.write().unwrap().insert(
+            block,
+            Quorum {
+                operators,
+                aggregated_public_key,
+            },
+        );
+        *self.latest_block.write().unwrap() = block;
+        Ok(())
+    }
+
+    // Fetch and cache the quorum valid at `block`, if it isn't already cached. Returns the
+    // block the cache entry actually lives under: the subgraph is asked to resolve `block`,
+    // but isn't guaranteed to echo back exactly that number (e.g. an unindexed/future block,
+    // or indexer lag), so callers must key their cache lookup off the returned value rather
+    // than assuming it matches their request.
+    async fn load_quorum(&self, block: u64) -> Result<u64, Box<dyn std::error::Error>> {
+        if self.quorum_cache.read().unwrap().contains_key(&block) {
+            return Ok(block);
         }
 
-        let mut public_key = self.aggregated_public_key;
-        for nonsigner in nonsigners {
-            public_key = public_key - self.operators.get(&nonsigner).unwrap().public_key_g2.unwrap();
+        let (resolved_block, operators) = get_operators(&self.client, Some(block)).await?;
+        let aggregated_public_key = aggregate_public_key(&operators);
+        self.quorum_cache.write().unwrap().insert(
+            resolved_block,
+            Quorum {
+                operators,
+                aggregated_public_key,
+            },
+        );
+        Ok(resolved_block)
+    }
+
+    // Verify a BLS aggregate signature against the quorum that was active at `block`,
+    // fetching and caching it first if it hasn't been loaded yet. This is what keeps
+    // verification correct across operator registrations, deregistrations, and restakes:
+    // like a fork-aware consensus client picking state for the right epoch, the aggregate
+    // key used here must match the quorum that actually signed at `block`.
+    pub async fn verify_signature_at(
+        &self,
+        block: u64,
+        message: &str,
+        signature_hex: &str,
+        nonsigners: Vec<String>,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        let resolved_block = self.load_quorum(block).await?;
+
+        let cache = self.quorum_cache.read().unwrap();
+        let quorum = cache
+            .get(&resolved_block)
+            .ok_or("quorum missing from cache immediately after being loaded")?;
+        Ok(Self::verify_with_quorum(quorum, self.threshold_percent, message, signature_hex, nonsigners)?)
+    }
+
+    // Verify a BLS aggregate signature against the latest cached quorum, honoring the
+    // supplied nonsigners. A thin wrapper over `verify_with_quorum` using the quorum at
+    // `latest_block`, for callers that don't need to pin verification to a specific block.
+    pub fn verify_signature(
+        &self,
+        message: &str,
+        signature_hex: &str,
+        nonsigners: Vec<String>,
+    ) -> Result<bool, VerificationError> {
+        let latest_block = *self.latest_block.read().unwrap();
+        let cache = self.quorum_cache.read().unwrap();
+        let quorum = cache.get(&latest_block).expect("latest block is always cached");
+        Self::verify_with_quorum(quorum, self.threshold_percent, message, signature_hex, nonsigners)
+    }
+
+    fn verify_with_quorum(
+        quorum: &Quorum,
+        threshold_percent: f64,
+        message: &str,
+        signature_hex: &str,
+        nonsigners: Vec<String>,
+    ) -> Result<bool, VerificationError> {
+        let total_stake: f64 = quorum.operators.values().map(|op| op.stake).sum();
+
+        // Looking up each nonsigner also validates the caller's list: an id that isn't part
+        // of this quorum (e.g. it was dropped for failing key validation) is reported instead
+        // of panicking on attacker- or node-influenced input.
+        let mut nonsigners_stake = 0.0;
+        let mut nonsigner_keys = Vec::with_capacity(nonsigners.len());
+        for id in &nonsigners {
+            let operator = quorum
+                .operators
+                .get(id)
+                .ok_or_else(|| VerificationError::UnknownNonsigner(id.clone()))?;
+            nonsigners_stake += operator.stake;
+            nonsigner_keys.push(operator.public_key_g2);
+        }
+
+        // Cheap stake-threshold check first; no point paying for a pairing we'll reject anyway.
+        if (100.0 * nonsigners_stake / total_stake) > (100.0 - threshold_percent) {
+            return Ok(false);
         }
 
-        // Replace with BLS signature decoding and verification
-        let message_hash = hash(message);
-        let signature = G2Affine::identity(); // Placeholder
-        pairing(&G2Prepared::from(public_key), &signature).is_zero() // Adjust this line with the actual BLS verification logic
+        let mut apk = G2Projective::from(quorum.aggregated_public_key);
+        for key in nonsigner_keys {
+            apk -= key;
+        }
+        let apk = G2Affine::from(apk);
+
+        let signature = match decode_g1_compressed(signature_hex) {
+            Some(point) => point,
+            None => return Ok(false),
+        };
+
+        let message_point =
+            <G1Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(message.as_bytes(), SIG_DST);
+
+        // e(sigma, g2) == e(H(m), apk)  <=>  e(sigma, g2) * e(-H(m), apk) == 1
+        let neg_message_point = G1Affine::from(-message_point);
+        let pairing_product = multi_miller_loop(&[
+            (&signature, &G2Prepared::from(G2Affine::generator())),
+            (&neg_message_point, &G2Prepared::from(apk)),
+        ]);
+
+        Ok(pairing_product.final_exponentiation().is_identity().into())
     }
 
-    // Fetch finalized batches (simplified version)
-    pub async fn get_finalized(&self, after: i32, chaining_hash: Option<String>) -> Result<(String, Vec<String>), Box<dyn std::error::Error>> {
-        let client = Client::new();
-        let mut res = Vec::new();
-        let mut index = if chaining_hash.is_some() { after } else { after - 1 };
+    // Stream finalized batches as they land, backing off with jitter instead of busy-polling
+    // an idle sequencer. Reuses `self.client` rather than opening a second one, so a
+    // `Zellular` instance keeps exactly one `reqwest::Client` for its whole lifetime.
+    pub fn finalized_stream(&self, after: i32) -> impl Stream<Item = Result<FinalizedBatch, Box<dyn std::error::Error>>> {
+        let client = self.client.clone();
+        let base_url = self.base_url.clone();
+        let app_name = self.app_name.clone();
 
-        loop {
-            let url = format!("{}/node/{}/batches/finalized?after={}", self.base_url, self.app_name, index);
-            let resp = client.get(&url).send().await?.json::<serde_json::Value>().await?;
+        try_stream! {
+            const MIN_BACKOFF: Duration = Duration::from_millis(200);
+            const MAX_BACKOFF: Duration = Duration::from_secs(10);
 
-            if resp["data"].is_null() {
-                continue;
+            let mut index = after - 1;
+            let mut chaining_hash = String::new();
+            let mut backoff = MIN_BACKOFF;
+
+            loop {
+                let url = format!("{}/node/{}/batches/finalized?after={}", base_url, app_name, index);
+                let resp = client.get(&url).send().await?.json::<serde_json::Value>().await?;
+
+                if resp["data"].is_null() {
+                    let jitter = Duration::from_millis(rand::thread_rng().gen_range(0..100));
+                    tokio::time::sleep(backoff + jitter).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+                backoff = MIN_BACKOFF;
+
+                let batches = resp["data"]["batches"].as_array().cloned().unwrap_or_default();
+                for batch in batches {
+                    index += 1;
+                    let batch = batch.as_str().unwrap_or_default().to_string();
+                    chaining_hash += &hash(&batch);
+                    yield FinalizedBatch { index, batch, chaining_hash: chaining_hash.clone() };
+                }
             }
+        }
+    }
+
+    // Like `finalized_stream`, but checks that each batch's index is exactly one past the
+    // previous and ends the stream with `ChainBreak` otherwise. Those indices come from
+    // `finalized_stream`'s own count of the batches it receives, not from the node, so a batch
+    // the node skips or replaces is not detected. `from_index` is the index of the first batch
+    // expected to follow the anchor. Each yielded `FinalizedBatch` carries a chaining hash seeded from
+    // `expected_start_hash`, so callers can persist it as a checkpoint and resume verification
+    // across restarts — note this hash is recomputed locally from the caller's own anchor, not
+    // checked against any independent value, since the node's API doesn't expose one.
+    pub fn verify_chain(
+        &self,
+        expected_start_hash: String,
+        from_index: i32,
+    ) -> impl Stream<Item = Result<FinalizedBatch, FinalizationError>> {
+        let inner = self.finalized_stream(from_index);
 
-            let batches = resp["data"]["batches"].as_array().unwrap_or(&vec![]);
-            let finalized = &resp["data"]["finalized"];
+        try_stream! {
+            futures::pin_mut!(inner);
+            let mut expected_index = from_index - 1;
+            let mut chaining_hash = expected_start_hash;
 
-            for batch in batches {
-                index += 1;
-                let chaining_hash = chaining_hash.clone().unwrap_or_else(|| "".to_string()) + &hash(batch.as_str().unwrap());
-                res.push(batch.as_str().unwrap().to_string());
+            while let Some(item) = inner.next().await {
+                let item = item.map_err(FinalizationError::Upstream)?;
+                expected_index += 1;
 
-                if finalized != &serde_json::Value::Null && index == finalized["index"].as_i64().unwrap() as i32 {
-                    return Ok((chaining_hash, res));
+                if item.index != expected_index {
+                    Err(FinalizationError::ChainBreak {
+                        expected_index,
+                        found_index: item.index,
+                    })?;
                 }
+
+                let extended_hash = chaining_hash.clone() + &hash(&item.batch);
+                chaining_hash = extended_hash.clone();
+
+                yield FinalizedBatch {
+                    index: item.index,
+                    batch: item.batch,
+                    chaining_hash: extended_hash,
+                };
             }
         }
     }
@@ -160,17 +647,279 @@ impl Zellular {
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let client = Client::new();
-    let operators = get_operators(&client).await?;
+    let (_, operators) = get_operators(&client, None).await?;
     let base_url = operators.values().next().unwrap().socket.clone();
 
     println!("Base URL: {}", base_url);
 
     let verifier = Zellular::new("simple_app", &base_url, 67.0).await?;
-    let (chaining_hash, batches) = verifier.get_finalized(0, None).await?;
+    let stream = verifier.finalized_stream(0);
+    futures::pin_mut!(stream);
 
-    for (i, batch) in batches.iter().enumerate() {
-        println!("Batch {}: {}", i + 1, batch);
+    while let Some(item) = stream.next().await {
+        let item = item?;
+        println!("Batch {}: {}", item.index, item.batch);
     }
 
     Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decimal_to_be_bytes_round_trips_small_values() {
+        assert_eq!(decimal_to_be_bytes("0", 2), Some(vec![0, 0]));
+        assert_eq!(decimal_to_be_bytes("255", 1), Some(vec![255]));
+        assert_eq!(decimal_to_be_bytes("256", 2), Some(vec![1, 0]));
+        assert_eq!(decimal_to_be_bytes("65535", 2), Some(vec![255, 255]));
+    }
+
+    #[test]
+    fn decimal_to_be_bytes_trims_whitespace() {
+        assert_eq!(decimal_to_be_bytes("  42 ", 1), Some(vec![42]));
+    }
+
+    #[test]
+    fn decimal_to_be_bytes_rejects_overflow() {
+        // 256 doesn't fit in a single byte.
+        assert_eq!(decimal_to_be_bytes("256", 1), None);
+    }
+
+    #[test]
+    fn decimal_to_be_bytes_rejects_non_digits() {
+        assert_eq!(decimal_to_be_bytes("12a", 2), None);
+        assert_eq!(decimal_to_be_bytes("-1", 2), None);
+    }
+
+    #[test]
+    fn verify_inclusion_rejects_empty_leaf_set() {
+        assert!(!verify_inclusion("anything", &[], &[]));
+    }
+
+    #[test]
+    fn verify_inclusion_accepts_a_single_leaf_tree() {
+        // A tree of exactly one leaf: the leaf hash *is* the root, at generalized index 1.
+        let root = hash("only-leaf");
+        assert!(verify_inclusion(&root, &[(1, "only-leaf".to_string())], &[]));
+    }
+
+    // Build a depth-2, 4-leaf tree (generalized indices 4,5,6,7 under root 1) out of raw
+    // leaves, returning (root, per-index hash) so tests can construct proofs against it.
+    fn build_tree(leaves: [&str; 4]) -> (String, HashMap<u64, String>) {
+        let mut nodes = HashMap::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            nodes.insert(4 + i as u64, hash(leaf));
+        }
+        let parent2 = hash(&(nodes[&4].clone() + &nodes[&5]));
+        let parent3 = hash(&(nodes[&6].clone() + &nodes[&7]));
+        nodes.insert(2, parent2);
+        nodes.insert(3, parent3);
+        let root = hash(&(nodes[&2].clone() + &nodes[&3]));
+        nodes.insert(1, root.clone());
+        (root, nodes)
+    }
+
+    #[test]
+    fn verify_inclusion_accepts_a_valid_multiproof() {
+        let (root, nodes) = build_tree(["a", "b", "c", "d"]);
+
+        // Proving leaves 4 ("a") and 7 ("d"): processing descending generalized index pulls
+        // the index-6 sibling ("c") from the proof first, then the index-5 sibling ("b").
+        let leaves = vec![(4u64, "a".to_string()), (7u64, "d".to_string())];
+        let proof = vec![nodes[&6].clone(), nodes[&5].clone()];
+
+        assert!(verify_inclusion(&root, &leaves, &proof));
+    }
+
+    #[test]
+    fn verify_inclusion_rejects_a_tampered_proof_hash() {
+        let (root, nodes) = build_tree(["a", "b", "c", "d"]);
+        let leaves = vec![(4u64, "a".to_string()), (7u64, "d".to_string())];
+        let mut proof = vec![nodes[&6].clone(), nodes[&5].clone()];
+        proof[0] = hash("not-c");
+
+        assert!(!verify_inclusion(&root, &leaves, &proof));
+    }
+
+    #[test]
+    fn verify_inclusion_rejects_a_mismatched_root() {
+        let (_, nodes) = build_tree(["a", "b", "c", "d"]);
+        let leaves = vec![(4u64, "a".to_string()), (7u64, "d".to_string())];
+        let proof = vec![nodes[&6].clone(), nodes[&5].clone()];
+
+        assert!(!verify_inclusion("not-the-root", &leaves, &proof));
+    }
+
+    #[test]
+    fn verify_inclusion_rejects_leftover_proof_entries() {
+        let (root, _nodes) = build_tree(["a", "b", "c", "d"]);
+        // All four leaves are known, so the whole tree reconstructs with no proof at all; a
+        // caller that supplies an extra, unconsumed proof hash should be rejected.
+        let leaves = vec![
+            (4u64, "a".to_string()),
+            (5u64, "b".to_string()),
+            (6u64, "c".to_string()),
+            (7u64, "d".to_string()),
+        ];
+        let proof = vec![hash("unused")];
+
+        assert!(!verify_inclusion(&root, &leaves, &proof));
+    }
+
+    use bls12_381::Scalar;
+
+    // Inverse of `decimal_to_be_bytes`: render a big-endian byte string as a base-10 string,
+    // so a curve point computed in a test can be round-tripped through the same decimal
+    // coordinate encoding the subgraph sends over the wire.
+    fn be_bytes_to_decimal(bytes: &[u8]) -> String {
+        let mut digits: Vec<u8> = vec![0];
+        for &byte in bytes {
+            let mut carry = byte as u32;
+            for digit in digits.iter_mut() {
+                let total = *digit as u32 * 256 + carry;
+                *digit = (total % 10) as u8;
+                carry = total / 10;
+            }
+            while carry > 0 {
+                digits.push((carry % 10) as u8);
+                carry /= 10;
+            }
+        }
+        digits.iter().rev().map(|d| (d + b'0') as char).collect()
+    }
+
+    // A deterministic keypair derived from a fixed scalar, for signing/verification tests.
+    fn test_keypair(seed: u64) -> (Scalar, G1Affine, G2Affine) {
+        let sk = Scalar::from(seed);
+        let pk_g1 = G1Affine::from(G1Affine::generator() * sk);
+        let pk_g2 = G2Affine::from(G2Affine::generator() * sk);
+        (sk, pk_g1, pk_g2)
+    }
+
+    // An `OperatorRecord` carrying `pk_g1`/`pk_g2` encoded the way the subgraph sends them:
+    // decimal coordinate strings, G2 as `[c1, c0]`.
+    fn operator_record_with_keys(pk_g1: G1Affine, pk_g2: G2Affine) -> OperatorRecord {
+        let g1 = pk_g1.to_uncompressed();
+        let g2 = pk_g2.to_uncompressed();
+        OperatorRecord {
+            id: "op".to_string(),
+            operator_id: "1".to_string(),
+            pubkey_g1_x: vec![be_bytes_to_decimal(&g1[..48])],
+            pubkey_g1_y: vec![be_bytes_to_decimal(&g1[48..])],
+            pubkey_g2_x: vec![be_bytes_to_decimal(&g2[..48]), be_bytes_to_decimal(&g2[48..96])],
+            pubkey_g2_y: vec![be_bytes_to_decimal(&g2[96..144]), be_bytes_to_decimal(&g2[144..])],
+            socket: String::new(),
+            stake: 0.0,
+        }
+    }
+
+    #[test]
+    fn decode_operator_keys_accepts_a_valid_keypair() {
+        let (_, pk_g1, pk_g2) = test_keypair(424242);
+        let record = operator_record_with_keys(pk_g1, pk_g2);
+
+        let (decoded_g1, decoded_g2) = decode_operator_keys(&record).unwrap();
+        assert_eq!(decoded_g1, pk_g1);
+        assert_eq!(decoded_g2, pk_g2);
+    }
+
+    #[test]
+    fn decode_operator_keys_rejects_an_off_curve_point() {
+        let (_, _, pk_g2) = test_keypair(424242);
+        let mut record = operator_record_with_keys(G1Affine::generator(), pk_g2);
+        // y^2 = x^3 + 4 doesn't hold for (0, 1): 1 != 4.
+        record.pubkey_g1_x = vec!["0".to_string()];
+        record.pubkey_g1_y = vec!["1".to_string()];
+
+        assert!(matches!(decode_operator_keys(&record), Err(OperatorKeyError::NotOnCurve)));
+    }
+
+    #[test]
+    fn decode_operator_keys_rejects_a_point_outside_the_prime_order_subgroup() {
+        let (_, _, pk_g2) = test_keypair(424242);
+        let mut record = operator_record_with_keys(G1Affine::generator(), pk_g2);
+        // (0, 2) satisfies y^2 = x^3 + 4 (4 == 4) but has small order and isn't a member of
+        // the prime-order subgroup. `G1Affine::from_uncompressed` checks on-curve and
+        // torsion-free together, so this is rejected as `NotOnCurve` rather than reaching the
+        // separate `is_torsion_free` check below it -- it never gets to construct a `G1Affine`
+        // to call that check on in the first place.
+        record.pubkey_g1_x = vec!["0".to_string()];
+        record.pubkey_g1_y = vec!["2".to_string()];
+
+        assert!(matches!(decode_operator_keys(&record), Err(OperatorKeyError::NotOnCurve)));
+    }
+
+    #[test]
+    fn decode_operator_keys_rejects_a_proof_of_possession_mismatch() {
+        let (_, pk_g1, _) = test_keypair(111);
+        let (_, _, pk_g2) = test_keypair(222);
+        let record = operator_record_with_keys(pk_g1, pk_g2);
+
+        assert!(matches!(
+            decode_operator_keys(&record),
+            Err(OperatorKeyError::ProofOfPossessionMismatch)
+        ));
+    }
+
+    // A single-operator quorum holding all the stake, for `verify_with_quorum` tests.
+    fn quorum_of_one(pk_g2: G2Affine) -> Quorum {
+        let mut operators = HashMap::new();
+        operators.insert(
+            "op".to_string(),
+            Operator {
+                id: "op".to_string(),
+                operator_id: "1".to_string(),
+                socket: String::new(),
+                stake: 1.0,
+                public_key_g1: G1Affine::identity(),
+                public_key_g2: pk_g2,
+            },
+        );
+        Quorum {
+            operators,
+            aggregated_public_key: pk_g2,
+        }
+    }
+
+    fn sign(sk: Scalar, message: &str) -> String {
+        let message_point =
+            <G1Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(message.as_bytes(), SIG_DST);
+        let signature = G1Affine::from(message_point * sk);
+        hex::encode(signature.to_compressed())
+    }
+
+    #[test]
+    fn verify_with_quorum_accepts_a_valid_aggregate_signature() {
+        let (sk, _, pk_g2) = test_keypair(13);
+        let quorum = quorum_of_one(pk_g2);
+        let signature_hex = sign(sk, "finalize-batch-7");
+
+        let result = Zellular::verify_with_quorum(&quorum, 67.0, "finalize-batch-7", &signature_hex, vec![]);
+        assert!(matches!(result, Ok(true)));
+    }
+
+    #[test]
+    fn verify_with_quorum_rejects_a_signature_over_a_different_message() {
+        let (sk, _, pk_g2) = test_keypair(13);
+        let quorum = quorum_of_one(pk_g2);
+        // Signed over a different message than the one we verify against, so the pairing
+        // equation e(sigma, g2) == e(H(m), apk) doesn't hold even though the key is valid.
+        let signature_hex = sign(sk, "finalize-batch-7");
+
+        let result = Zellular::verify_with_quorum(&quorum, 67.0, "finalize-batch-8", &signature_hex, vec![]);
+        assert!(matches!(result, Ok(false)));
+    }
+
+    #[test]
+    fn verify_with_quorum_rejects_a_signature_from_the_wrong_key() {
+        let (_, _, pk_g2) = test_keypair(13);
+        let (wrong_sk, _, _) = test_keypair(14);
+        let quorum = quorum_of_one(pk_g2);
+        let signature_hex = sign(wrong_sk, "finalize-batch-7");
+
+        let result = Zellular::verify_with_quorum(&quorum, 67.0, "finalize-batch-7", &signature_hex, vec![]);
+        assert!(matches!(result, Ok(false)));
+    }
 }
\ No newline at end of file